@@ -5,55 +5,220 @@ mod msg_fns;
 mod oneof;
 mod scalar;
 
+use std::cell::RefCell;
 use std::fmt;
+use std::fmt::Display;
 use std::slice;
+use std::thread;
 
-use anyhow::{bail, ensure, Error};
 use proc_macro2::TokenStream;
-use quote::quote;
+use quote::{quote, ToTokens};
 use syn::{Attribute, Ident, Lit, LitBool, Meta, MetaList, MetaNameValue, NestedMeta, Type};
 
 use crate::field::msg_fns::MsgFns;
 use crate::options::Options;
 
+/// A context for accumulating errors encountered while parsing `#[prost(...)]`
+/// attributes.
+///
+/// Modeled on serde_derive's `Ctxt`: rather than returning on the first bad
+/// attribute, callers record an error against the offending tokens and keep
+/// parsing, so that `cx.check()` at the end of derive expansion can report
+/// every problem the user needs to fix at once.
+pub struct Ctxt {
+    errors: RefCell<Option<Vec<syn::Error>>>,
+}
+
+impl Ctxt {
+    pub fn new() -> Self {
+        Ctxt {
+            errors: RefCell::new(Some(Vec::new())),
+        }
+    }
+
+    /// Records an error with a span derived from `obj`.
+    pub fn error_spanned_by<A: ToTokens, T: Display>(&self, obj: A, msg: T) {
+        self.errors
+            .borrow_mut()
+            .as_mut()
+            .unwrap()
+            .push(syn::Error::new_spanned(obj.into_token_stream(), msg));
+    }
+
+    /// Records a `syn::Error` that was already constructed, e.g. by a parser.
+    pub fn syn_error(&self, err: syn::Error) {
+        self.errors.borrow_mut().as_mut().unwrap().push(err);
+    }
+
+    /// The number of errors recorded so far. Used to detect whether a call
+    /// that returned `None` did so because it didn't recognize the input, or
+    /// because it recognized it but failed to parse something about it (and
+    /// already reported that failure here).
+    fn error_count(&self) -> usize {
+        self.errors.borrow().as_ref().unwrap().len()
+    }
+
+    /// Consumes the context, combining all recorded errors into a single
+    /// `syn::Error` via `Error::combine`, if any were recorded.
+    pub fn check(self) -> Result<(), syn::Error> {
+        let mut errors = self.errors.borrow_mut().take().unwrap().into_iter();
+
+        let mut combined = match errors.next() {
+            Some(first) => first,
+            None => return Ok(()),
+        };
+
+        for rest in errors {
+            combined.combine(rest);
+        }
+
+        Err(combined)
+    }
+}
+
+impl Default for Ctxt {
+    fn default() -> Self {
+        Ctxt::new()
+    }
+}
+
+impl Drop for Ctxt {
+    fn drop(&mut self) {
+        if !thread::panicking() && self.errors.borrow().is_some() {
+            panic!("forgot to call Ctxt::check");
+        }
+    }
+}
+
 #[derive(Clone)]
 pub enum Field {
     /// A scalar field.
-    Scalar(scalar::Field),
+    Scalar(scalar::Field, Naming, Overrides),
     /// A message field.
-    Message(message::Field),
+    Message(message::Field, Naming, Overrides),
     /// A map field.
-    Map(map::Field),
+    Map(map::Field, Naming, Overrides),
     /// A oneof field.
-    Oneof(oneof::Field),
+    Oneof(oneof::Field, Naming, Overrides),
     /// A group field.
-    Group(group::Field),
+    Group(group::Field, Naming, Overrides),
     /// An ignored field.
     Ignore,
 }
 
+/// Field-level `#[prost(...)]` attribute state that applies uniformly across
+/// field types, rather than being specific to scalar/message/map/etc.
+#[derive(Clone)]
+pub struct Overrides {
+    /// A `#[prost(default = "path")]` override for a message or oneof
+    /// field's starting value, in place of `Default::default()`. Scalar
+    /// fields (including enumerations) already have their own `default`
+    /// handling in `scalar::Field`, so this is never set for them.
+    default: Option<TokenStream>,
+    /// A `#[prost(skip_if = "path")]` predicate that, when it returns `true`
+    /// for the field's current value, omits the field from the wire.
+    skip_if: Option<syn::Path>,
+    /// Additional tags, from a `#[prost(alias_tags = "2, 3")]` attribute,
+    /// that this field should also be recognized under when decoding.
+    alias_tags: Vec<u32>,
+}
+
+impl Overrides {
+    /// Parses the `Overrides` shared by every field type. `parse_default`
+    /// must only be `true` for message and oneof fields: scalar fields
+    /// (including enumerations) parse their own `default = "..."` literal
+    /// in `scalar::Field::new`, and reusing this generic parser for them
+    /// would collide with that and misinterpret their literals as paths.
+    fn new(cx: &Ctxt, attrs: &[Meta], parse_default: bool) -> Overrides {
+        let mut default = None;
+        let mut skip_if = None;
+        let mut alias_tags = None;
+        for attr in attrs {
+            if parse_default {
+                if let Some(expr) = default_attr(cx, attr) {
+                    set_option(cx, &mut default, expr, attr, "duplicate default attribute");
+                }
+            }
+            if let Some(path) = skip_if_attr(cx, attr) {
+                set_option(cx, &mut skip_if, path, attr, "duplicate skip_if attribute");
+            }
+            if let Some(tags) = alias_tags_attr(cx, attr) {
+                set_option(
+                    cx,
+                    &mut alias_tags,
+                    tags,
+                    attr,
+                    "duplicate alias_tags attribute",
+                );
+            }
+        }
+        Overrides {
+            default,
+            skip_if,
+            alias_tags: alias_tags.unwrap_or_default(),
+        }
+    }
+}
+
+/// The resolved external-name state for a field, used to compute its
+/// JSON/reflection name independently of its Rust identifier.
+#[derive(Clone)]
+pub struct Naming {
+    /// An explicit `#[prost(rename = "...")]` override, if present.
+    rename: Option<String>,
+    /// The container-level `rename_all` rule to fall back to otherwise.
+    rule: RenameRule,
+}
+
+impl Naming {
+    fn new(cx: &Ctxt, attrs: &[Meta], options: &Options) -> Naming {
+        let mut rename = None;
+        for attr in attrs {
+            if let Some(name) = rename_attr(cx, attr) {
+                set_option(cx, &mut rename, name, attr, "duplicate rename attribute");
+            }
+        }
+        Naming {
+            rename,
+            rule: options.rename_rule,
+        }
+    }
+
+    fn resolve(&self, ident: &Ident) -> String {
+        match self.rename {
+            Some(ref name) => name.clone(),
+            None => self.rule.apply_to_field(&ident.to_string()),
+        }
+    }
+}
+
 impl Field {
     /// Creates a new list of `Field`s from an iterator of field attributes.
     ///
-    /// If the meta items are invalid, an error will be returned.
+    /// Malformed attributes are reported on `cx` rather than aborting parsing,
+    /// so that all of a field's problems can be diagnosed in a single pass.
     pub fn new(
+        cx: &Ctxt,
         field_ty: Type,
         attrs: Vec<Attribute>,
         mut inferred_tag: Option<u32>,
         options: &Options,
-    ) -> Result<Vec<Field>, Error> {
-        let nested_attrs = prost_nested_attrs(attrs);
+    ) -> Vec<Field> {
+        let nested_attrs = prost_nested_attrs(cx, attrs);
         let mut fields = Vec::with_capacity(nested_attrs.len());
         let mut ignore = false;
 
         for attrs in nested_attrs {
-            let attrs = attrs?;
-
-            ensure!(
-                !ignore,
-                "ignore attribute used but other attributes were found: {:?}",
-                attrs
-            );
+            if ignore {
+                cx.error_spanned_by(
+                    attrs_tokens(&attrs),
+                    format!(
+                        "ignore attribute used but other attributes were found: {:?}",
+                        attrs
+                    ),
+                );
+                continue;
+            }
             if attrs.iter().any(|attr| word_attr("ignore", attr)) {
                 fields.push(Field::Ignore);
                 ignore = true;
@@ -61,127 +226,242 @@ impl Field {
                 continue;
             }
 
+            let naming = Naming::new(cx, &attrs, options);
+            let errors_before = cx.error_count();
             let field = if let Some(field) =
-                scalar::Field::new(&field_ty, &attrs, inferred_tag, options)?
+                scalar::Field::new(cx, &field_ty, &attrs, inferred_tag, options)
             {
-                Field::Scalar(field)
+                Field::Scalar(field, naming, Overrides::new(cx, &attrs, false))
             } else if let Some(field) =
-                message::Field::new(&field_ty, &attrs, inferred_tag, options)?
+                message::Field::new(cx, &field_ty, &attrs, inferred_tag, options)
             {
-                Field::Message(field)
-            } else if let Some(field) = map::Field::new(&field_ty, &attrs, inferred_tag, options)? {
-                Field::Map(field)
-            } else if let Some(field) = oneof::Field::new(&attrs)? {
-                Field::Oneof(field)
-            } else if let Some(field) = group::Field::new(&attrs, inferred_tag)? {
-                Field::Group(field)
+                Field::Message(field, naming, Overrides::new(cx, &attrs, true))
+            } else if let Some(field) =
+                map::Field::new(cx, &field_ty, &attrs, inferred_tag, options)
+            {
+                Field::Map(field, naming, Overrides::new(cx, &attrs, false))
+            } else if let Some(field) = oneof::Field::new(cx, &attrs) {
+                Field::Oneof(field, naming, Overrides::new(cx, &attrs, true))
+            } else if let Some(field) = group::Field::new(cx, &attrs, inferred_tag) {
+                Field::Group(field, naming, Overrides::new(cx, &attrs, false))
+            } else if cx.error_count() > errors_before {
+                // One of the candidates above recognized this field's type
+                // but failed to parse one of its sub-attributes, and already
+                // reported that on `cx`. Don't also claim "no type
+                // attribute" for the same field.
+                continue;
             } else {
-                bail!("no type attribute");
+                cx.error_spanned_by(attrs_tokens(&attrs), "no type attribute");
+                continue;
             };
 
-            inferred_tag = field.tags().iter().max().map(|t| t + 1).or(inferred_tag);
+            // Only the field's own tag(s) feed tag inference for the next
+            // field; alias tags exist purely to widen decode-side acceptance
+            // and must not shift where auto-numbering picks up next.
+            inferred_tag = field
+                .base_tags()
+                .iter()
+                .max()
+                .map(|t| t + 1)
+                .or(inferred_tag);
 
             fields.push(field);
         }
 
-        Ok(fields)
+        fields
     }
 
     /// Creates a new oneof `Field` from an iterator of field attributes.
     ///
-    /// If the meta items are invalid, an error will be returned.
-    /// If the field should be ignored, `None` is returned.
-    pub fn new_oneof(attrs: Vec<Attribute>, options: &Options) -> Result<Option<Field>, Error> {
-        let attrs = prost_attrs(attrs);
+    /// Malformed attributes are reported on `cx`. Returns `None` if no
+    /// recognized type attribute was found, or if one was found but its
+    /// attributes failed to parse.
+    pub fn new_oneof(cx: &Ctxt, attrs: Vec<Attribute>, options: &Options) -> Option<Field> {
+        let attrs = prost_attrs(cx, attrs);
+        let naming = Naming::new(cx, &attrs, options);
+        let errors_before = cx.error_count();
 
         // TODO: check for ignore attribute.
 
-        let field = if let Some(field) = scalar::Field::new_oneof(&attrs, options)? {
-            Field::Scalar(field)
-        } else if let Some(field) = message::Field::new_oneof(&attrs, options)? {
-            Field::Message(field)
-        } else if let Some(field) = map::Field::new_oneof(&attrs, options)? {
-            Field::Map(field)
-        } else if let Some(field) = group::Field::new_oneof(&attrs)? {
-            Field::Group(field)
+        if let Some(field) = scalar::Field::new_oneof(cx, &attrs, options) {
+            Some(Field::Scalar(
+                field,
+                naming,
+                Overrides::new(cx, &attrs, false),
+            ))
+        } else if let Some(field) = message::Field::new_oneof(cx, &attrs, options) {
+            Some(Field::Message(
+                field,
+                naming,
+                Overrides::new(cx, &attrs, true),
+            ))
+        } else if let Some(field) = map::Field::new_oneof(cx, &attrs, options) {
+            Some(Field::Map(field, naming, Overrides::new(cx, &attrs, false)))
+        } else if let Some(field) = group::Field::new_oneof(cx, &attrs) {
+            Some(Field::Group(
+                field,
+                naming,
+                Overrides::new(cx, &attrs, false),
+            ))
+        } else if cx.error_count() > errors_before {
+            // Already reported on `cx` by whichever candidate recognized the
+            // type; avoid piling on a second, misleading diagnostic.
+            None
         } else {
-            bail!("no type attribute for oneof field");
-        };
-
-        Ok(Some(field))
+            cx.error_spanned_by(attrs_tokens(&attrs), "no type attribute for oneof field");
+            None
+        }
     }
 
-    pub fn tags(&self) -> Vec<u32> {
+    /// Returns the field's own tag(s), as assigned or inferred, excluding any
+    /// `alias_tags`. Used to seed tag inference for subsequent fields.
+    fn base_tags(&self) -> Vec<u32> {
         match *self {
-            Field::Scalar(ref scalar) => vec![scalar.tag],
-            Field::Message(ref message) => vec![message.tag],
-            Field::Map(ref map) => vec![map.tag],
-            Field::Oneof(ref oneof) => oneof.tags.clone(),
-            Field::Group(ref group) => vec![group.tag],
+            Field::Scalar(ref scalar, ..) => vec![scalar.tag],
+            Field::Message(ref message, ..) => vec![message.tag],
+            Field::Map(ref map, ..) => vec![map.tag],
+            Field::Oneof(ref oneof, ..) => oneof.tags.clone(),
+            Field::Group(ref group, ..) => vec![group.tag],
             Field::Ignore => vec![],
         }
     }
 
+    /// Returns every tag this field should be recognized under when
+    /// decoding, including any `alias_tags`.
+    pub fn tags(&self) -> Vec<u32> {
+        let mut tags = self.base_tags();
+        if let Some(overrides) = self.overrides() {
+            tags.extend(overrides.alias_tags.iter().copied());
+        }
+        tags
+    }
+
     /// Returns a statement which encodes the field.
     pub fn encode(&self, ident: TokenStream) -> TokenStream {
-        match *self {
-            Field::Scalar(ref scalar) => scalar.encode(ident),
-            Field::Message(ref message) => message.encode(ident),
-            Field::Map(ref map) => map.encode(ident),
-            Field::Oneof(ref oneof) => oneof.encode(ident),
-            Field::Group(ref group) => group.encode(ident),
-            Field::Ignore => quote!(),
+        let encode = match *self {
+            Field::Scalar(ref scalar, ..) => scalar.encode(ident.clone()),
+            Field::Message(ref message, ..) => message.encode(ident.clone()),
+            Field::Map(ref map, ..) => map.encode(ident.clone()),
+            Field::Oneof(ref oneof, ..) => oneof.encode(ident.clone()),
+            Field::Group(ref group, ..) => group.encode(ident.clone()),
+            Field::Ignore => return quote!(),
+        };
+        match self
+            .overrides()
+            .and_then(|overrides| overrides.skip_if.as_ref())
+        {
+            Some(predicate) => quote! {
+                if !#predicate(&#ident) {
+                    #encode
+                }
+            },
+            None => encode,
         }
     }
 
     /// Returns an expression which evaluates to the result of merging a decoded
     /// value into the field.
     pub fn merge(&self, ident: TokenStream) -> TokenStream {
-        match *self {
-            Field::Scalar(ref scalar) => scalar.merge(ident),
-            Field::Message(ref message) => message.merge(ident),
-            Field::Map(ref map) => map.merge(ident),
-            Field::Oneof(ref oneof) => oneof.merge(ident),
-            Field::Group(ref group) => group.merge(ident),
-            Field::Ignore => quote!(),
+        let merge = match *self {
+            Field::Scalar(ref scalar, ..) => scalar.merge(ident.clone()),
+            Field::Message(ref message, ..) => message.merge(ident.clone()),
+            Field::Map(ref map, ..) => map.merge(ident.clone()),
+            Field::Oneof(ref oneof, ..) => oneof.merge(ident.clone()),
+            Field::Group(ref group, ..) => group.merge(ident.clone()),
+            Field::Ignore => return quote!(),
+        };
+        match self
+            .overrides()
+            .and_then(|overrides| overrides.default.clone())
+        {
+            // Seed the field with its default before merging the decoded
+            // value in, so the first merge into an unset field starts from
+            // the user's sentinel rather than `Default::default()`.
+            Some(default) => quote! {
+                {
+                    if #ident.is_none() {
+                        if let ::core::option::Option::Some(seed) = #default {
+                            #ident.get_or_insert(seed);
+                        }
+                    }
+                    #merge
+                }
+            },
+            None => merge,
         }
     }
 
     /// Returns an expression which evaluates to the encoded length of the field.
     pub fn encoded_len(&self, ident: TokenStream) -> TokenStream {
-        match *self {
-            Field::Scalar(ref scalar) => scalar.encoded_len(ident),
-            Field::Map(ref map) => map.encoded_len(ident),
-            Field::Message(ref msg) => msg.encoded_len(ident),
-            Field::Oneof(ref oneof) => oneof.encoded_len(ident),
-            Field::Group(ref group) => group.encoded_len(ident),
-            Field::Ignore => quote!(0),
+        let encoded_len = match *self {
+            Field::Scalar(ref scalar, ..) => scalar.encoded_len(ident.clone()),
+            Field::Map(ref map, ..) => map.encoded_len(ident.clone()),
+            Field::Message(ref msg, ..) => msg.encoded_len(ident.clone()),
+            Field::Oneof(ref oneof, ..) => oneof.encoded_len(ident.clone()),
+            Field::Group(ref group, ..) => group.encoded_len(ident.clone()),
+            Field::Ignore => return quote!(0),
+        };
+        match self
+            .overrides()
+            .and_then(|overrides| overrides.skip_if.as_ref())
+        {
+            Some(predicate) => quote! {
+                if #predicate(&#ident) {
+                    0
+                } else {
+                    #encoded_len
+                }
+            },
+            None => encoded_len,
         }
     }
 
     /// Returns a statement which clears the field.
     pub fn clear(&self, ident: TokenStream) -> TokenStream {
+        if let Some(default) = self
+            .overrides()
+            .and_then(|overrides| overrides.default.clone())
+        {
+            return quote!(#ident = #default;);
+        }
         match *self {
-            Field::Scalar(ref scalar) => scalar.clear(ident),
-            Field::Message(ref message) => message.clear(ident),
-            Field::Map(ref map) => map.clear(ident),
-            Field::Oneof(ref oneof) => oneof.clear(ident),
-            Field::Group(ref group) => group.clear(ident),
+            Field::Scalar(ref scalar, ..) => scalar.clear(ident),
+            Field::Message(ref message, ..) => message.clear(ident),
+            Field::Map(ref map, ..) => map.clear(ident),
+            Field::Oneof(ref oneof, ..) => oneof.clear(ident),
+            Field::Group(ref group, ..) => group.clear(ident),
             Field::Ignore => quote!(),
         }
     }
 
     pub fn default(&self) -> TokenStream {
+        if let Some(default) = self
+            .overrides()
+            .and_then(|overrides| overrides.default.clone())
+        {
+            return default;
+        }
         match *self {
-            Field::Scalar(ref scalar) => scalar.default(),
+            Field::Scalar(ref scalar, ..) => scalar.default(),
             _ => quote!(::core::default::Default::default()),
         }
     }
 
+    fn overrides(&self) -> Option<&Overrides> {
+        match *self {
+            Field::Scalar(_, _, ref overrides)
+            | Field::Message(_, _, ref overrides)
+            | Field::Map(_, _, ref overrides)
+            | Field::Oneof(_, _, ref overrides)
+            | Field::Group(_, _, ref overrides) => Some(overrides),
+            Field::Ignore => None,
+        }
+    }
+
     /// Produces the fragment implementing debug for the given field.
     pub fn debug(&self, ident: TokenStream) -> TokenStream {
         match *self {
-            Field::Scalar(ref scalar) => {
+            Field::Scalar(ref scalar, ..) => {
                 let wrapper = scalar.debug(quote!(ScalarWrapper));
                 quote! {
                     {
@@ -190,7 +470,7 @@ impl Field {
                     }
                 }
             }
-            Field::Map(ref map) => {
+            Field::Map(ref map, ..) => {
                 let wrapper = map.debug(quote!(MapWrapper));
                 quote! {
                     {
@@ -199,18 +479,33 @@ impl Field {
                     }
                 }
             }
-            Field::Message(ref message) => message.debug(ident),
+            Field::Message(ref message, ..) => message.debug(ident),
             _ => quote!(&#ident),
         }
     }
 
     pub fn methods(&self, ident: &Ident) -> Option<TokenStream> {
         match *self {
-            Field::Scalar(ref scalar) => scalar.methods(ident),
-            Field::Map(ref map) => map.methods(ident),
+            Field::Scalar(ref scalar, ..) => scalar.methods(ident),
+            Field::Map(ref map, ..) => map.methods(ident),
             _ => None,
         }
     }
+
+    /// Returns the external (JSON/reflection) name for this field, computed
+    /// from any explicit `rename` override or, failing that, the container's
+    /// `rename_all` rule applied to `ident`. Ignored fields return `ident`
+    /// unchanged.
+    pub fn json_name(&self, ident: &Ident) -> String {
+        match *self {
+            Field::Scalar(_, ref naming, _)
+            | Field::Message(_, ref naming, _)
+            | Field::Map(_, ref naming, _)
+            | Field::Oneof(_, ref naming, _)
+            | Field::Group(_, ref naming, _) => naming.resolve(ident),
+            Field::Ignore => ident.to_string(),
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -264,7 +559,11 @@ impl fmt::Display for Label {
 }
 
 /// Get the items belonging to the 'prost' list attribute, e.g. `#[prost(foo, bar="baz")]`.
-pub fn prost_attrs(attrs: Vec<Attribute>) -> Vec<Meta> {
+///
+/// Malformed items inside a `prost(...)` list are reported on `cx` and
+/// otherwise skipped. Attributes that fail to parse as a `Meta` at all are
+/// skipped without being reported.
+pub fn prost_attrs(cx: &Ctxt, attrs: Vec<Attribute>) -> Vec<Meta> {
     attrs
         .iter()
         .flat_map(Attribute::parse_meta)
@@ -278,10 +577,11 @@ pub fn prost_attrs(attrs: Vec<Attribute>) -> Vec<Meta> {
             }
             _ => Vec::new(),
         })
-        .flat_map(|attr| -> Result<_, _> {
-            match attr {
-                NestedMeta::Meta(attr) => Ok(attr),
-                NestedMeta::Lit(lit) => bail!("invalid prost attribute: {:?}", lit),
+        .filter_map(|attr| match attr {
+            NestedMeta::Meta(attr) => Some(attr),
+            NestedMeta::Lit(lit) => {
+                cx.error_spanned_by(&lit, format!("invalid prost attribute: {:?}", lit));
+                None
             }
         })
         .collect()
@@ -292,82 +592,103 @@ pub fn prost_attrs(attrs: Vec<Attribute>) -> Vec<Meta> {
 /// #[prost(foo, bar="baz")]
 /// #[prost(bar, foo="baz")]
 /// ```
-fn prost_nested_attrs(attrs: Vec<Attribute>) -> Vec<Result<Vec<Meta>, Error>> {
+///
+/// Any malformed items are reported on `cx` and otherwise skipped.
+fn prost_nested_attrs(cx: &Ctxt, attrs: Vec<Attribute>) -> Vec<Vec<Meta>> {
     attrs
         .iter()
         .filter_map(|attr| match Attribute::parse_meta(attr) {
-            Ok(meta) => match meta {
-                Meta::List(MetaList { path, nested, .. }) if path.is_ident("prost") => {
-                    let mut attrs = Vec::with_capacity(nested.len());
-                    nested
-                        .into_iter()
-                        .try_for_each(|attr| match attr {
-                            NestedMeta::Meta(attr) => {
-                                attrs.push(attr);
-                                Ok(())
-                            }
-                            NestedMeta::Lit(lit) => bail!("invalid prost attribute: {:?}", lit),
-                        })
-                        .map(|_| attrs)
-                        .into()
+            Ok(Meta::List(MetaList { path, nested, .. })) if path.is_ident("prost") => {
+                let mut attrs = Vec::with_capacity(nested.len());
+                for attr in nested {
+                    match attr {
+                        NestedMeta::Meta(attr) => attrs.push(attr),
+                        NestedMeta::Lit(lit) => {
+                            cx.error_spanned_by(&lit, format!("invalid prost attribute: {:?}", lit))
+                        }
+                    }
                 }
-                _ => None,
-            },
-            Err(err) => Some(Err(err.into())),
+                Some(attrs)
+            }
+            Ok(_) => None,
+            Err(err) => {
+                cx.syn_error(err);
+                None
+            }
         })
         .collect()
 }
 
-pub fn set_option<T>(option: &mut Option<T>, value: T, message: &str) -> Result<(), Error>
-where
+/// Renders a list of `Meta`s as tokens suitable for anchoring a span, for use
+/// when no single attribute is obviously at fault.
+fn attrs_tokens(attrs: &[Meta]) -> TokenStream {
+    attrs.iter().map(Meta::to_token_stream).collect()
+}
+
+pub fn set_option<T>(
+    cx: &Ctxt,
+    option: &mut Option<T>,
+    value: T,
+    tokens: impl ToTokens,
+    message: &str,
+) where
     T: fmt::Debug,
 {
     if let Some(ref existing) = *option {
-        bail!("{}: {:?} and {:?}", message, existing, value);
+        cx.error_spanned_by(
+            tokens,
+            format!("{}: {:?} and {:?}", message, existing, value),
+        );
+    } else {
+        *option = Some(value);
     }
-    *option = Some(value);
-    Ok(())
 }
 
-pub fn set_bool(b: &mut bool, message: &str) -> Result<(), Error> {
+pub fn set_bool(cx: &Ctxt, b: &mut bool, tokens: impl ToTokens, message: &str) {
     if *b {
-        bail!("{}", message);
+        cx.error_spanned_by(tokens, message);
     } else {
         *b = true;
-        Ok(())
     }
 }
 
 /// Unpacks an attribute into a (key, boolean) pair, returning the boolean value.
-/// If the key doesn't match the attribute, `None` is returned.
-pub fn bool_attr(key: &str, attr: &Meta) -> Result<Option<bool>, Error> {
+/// If the key doesn't match the attribute, `None` is returned. If the value is
+/// malformed, the error is reported on `cx` and `None` is returned.
+pub fn bool_attr(cx: &Ctxt, key: &str, attr: &Meta) -> Option<bool> {
     if !attr.path().is_ident(key) {
-        return Ok(None);
+        return None;
     }
     match *attr {
-        Meta::Path(..) => Ok(Some(true)),
+        Meta::Path(..) => Some(true),
         Meta::List(ref meta_list) => {
             // TODO(rustlang/rust#23121): slice pattern matching would make this much nicer.
             if meta_list.nested.len() == 1 {
                 if let NestedMeta::Lit(Lit::Bool(LitBool { value, .. })) = meta_list.nested[0] {
-                    return Ok(Some(value));
+                    return Some(value);
                 }
             }
-            bail!("invalid {} attribute", key);
+            cx.error_spanned_by(attr, format!("invalid {} attribute", key));
+            None
         }
         Meta::NameValue(MetaNameValue {
             lit: Lit::Str(ref lit),
             ..
-        }) => lit
-            .value()
-            .parse::<bool>()
-            .map_err(Error::from)
-            .map(Option::Some),
+        }) => match lit.value().parse::<bool>() {
+            Ok(value) => Some(value),
+            Err(err) => {
+                cx.error_spanned_by(lit, err);
+                None
+            }
+        },
         Meta::NameValue(MetaNameValue {
             lit: Lit::Bool(LitBool { value, .. }),
             ..
-        }) => Ok(Some(value)),
-        _ => bail!("invalid {} attribute", key),
+        }) => Some(value),
+        _ => {
+            cx.error_spanned_by(attr, format!("invalid {} attribute", key));
+            None
+        }
     }
 }
 
@@ -380,58 +701,350 @@ pub fn word_attr(key: &str, attr: &Meta) -> bool {
     }
 }
 
-pub(super) fn tag_attr(attr: &Meta) -> Result<Option<u32>, Error> {
+/// Parses a `tag = "..."` or `tag(...)` attribute. If the value is malformed,
+/// the error is reported on `cx` and `None` is returned.
+pub(super) fn tag_attr(cx: &Ctxt, attr: &Meta) -> Option<u32> {
     if !attr.path().is_ident("tag") {
-        return Ok(None);
+        return None;
     }
     match *attr {
         Meta::List(ref meta_list) => {
             // TODO(rustlang/rust#23121): slice pattern matching would make this much nicer.
             if meta_list.nested.len() == 1 {
                 if let NestedMeta::Lit(Lit::Int(ref lit)) = meta_list.nested[0] {
-                    return Ok(Some(lit.base10_parse()?));
+                    return match lit.base10_parse() {
+                        Ok(tag) => Some(tag),
+                        Err(err) => {
+                            cx.syn_error(err);
+                            None
+                        }
+                    };
                 }
             }
-            bail!("invalid tag attribute: {:?}", attr);
+            cx.error_spanned_by(attr, format!("invalid tag attribute: {:?}", attr));
+            None
         }
         Meta::NameValue(ref meta_name_value) => match meta_name_value.lit {
-            Lit::Str(ref lit) => lit
-                .value()
-                .parse::<u32>()
-                .map_err(Error::from)
-                .map(Option::Some),
-            Lit::Int(ref lit) => Ok(Some(lit.base10_parse()?)),
-            _ => bail!("invalid tag attribute: {:?}", attr),
+            Lit::Str(ref lit) => match lit.value().parse::<u32>() {
+                Ok(tag) => Some(tag),
+                Err(err) => {
+                    cx.error_spanned_by(lit, err);
+                    None
+                }
+            },
+            Lit::Int(ref lit) => match lit.base10_parse() {
+                Ok(tag) => Some(tag),
+                Err(err) => {
+                    cx.syn_error(err);
+                    None
+                }
+            },
+            _ => {
+                cx.error_spanned_by(attr, format!("invalid tag attribute: {:?}", attr));
+                None
+            }
         },
-        _ => bail!("invalid tag attribute: {:?}", attr),
+        _ => {
+            cx.error_spanned_by(attr, format!("invalid tag attribute: {:?}", attr));
+            None
+        }
     }
 }
 
-fn tags_attr(attr: &Meta) -> Result<Option<Vec<u32>>, Error> {
+fn tags_attr(cx: &Ctxt, attr: &Meta) -> Option<Vec<u32>> {
     if !attr.path().is_ident("tags") {
-        return Ok(None);
+        return None;
     }
+    parse_u32_list(cx, attr, "tag")
+}
+
+/// Parses a comma-separated list of `u32`s out of either a `name(1, 2, 3)`
+/// meta list or a `name = "1, 2, 3"` string, as used by both `tags` and
+/// `alias_tags`. `label` is used only to phrase the error message.
+fn parse_u32_list(cx: &Ctxt, attr: &Meta, label: &str) -> Option<Vec<u32>> {
     match *attr {
         Meta::List(ref meta_list) => {
-            let mut tags = Vec::with_capacity(meta_list.nested.len());
+            let mut values = Vec::with_capacity(meta_list.nested.len());
             for item in &meta_list.nested {
                 if let NestedMeta::Lit(Lit::Int(ref lit)) = *item {
-                    tags.push(lit.base10_parse()?);
+                    match lit.base10_parse() {
+                        Ok(value) => values.push(value),
+                        Err(err) => cx.syn_error(err),
+                    }
                 } else {
-                    bail!("invalid tag attribute: {:?}", attr);
+                    cx.error_spanned_by(attr, format!("invalid {} attribute: {:?}", label, attr));
+                    return None;
+                }
+            }
+            Some(values)
+        }
+        Meta::NameValue(MetaNameValue {
+            lit: Lit::Str(ref lit),
+            ..
+        }) => {
+            let mut values = Vec::new();
+            for s in lit.value().split(',') {
+                match s.trim().parse::<u32>() {
+                    Ok(value) => values.push(value),
+                    Err(err) => {
+                        cx.error_spanned_by(lit, err);
+                        return None;
+                    }
                 }
             }
-            Ok(Some(tags))
+            Some(values)
+        }
+        _ => {
+            cx.error_spanned_by(attr, format!("invalid {} attribute: {:?}", label, attr));
+            None
+        }
+    }
+}
+
+/// Parses a `default = "path"` attribute into a call to the named function,
+/// for use by message and oneof fields whose zero value isn't simply
+/// `Default::default()`. Mirrors serde's `default = "path"`. It is not used
+/// for scalar fields, enumerations included: those keep the `default = "..."`
+/// literal parsed by `scalar::Field`.
+pub(super) fn default_attr(cx: &Ctxt, attr: &Meta) -> Option<TokenStream> {
+    if !attr.path().is_ident("default") {
+        return None;
+    }
+    match *attr {
+        Meta::NameValue(MetaNameValue {
+            lit: Lit::Str(ref lit),
+            ..
+        }) => match lit.parse::<syn::Path>() {
+            Ok(path) => Some(quote!(#path())),
+            Err(err) => {
+                cx.error_spanned_by(lit, err);
+                None
+            }
+        },
+        _ => {
+            cx.error_spanned_by(
+                attr,
+                "invalid default attribute, expected `default = \"path\"`",
+            );
+            None
         }
+    }
+}
+
+/// Parses a `skip_if = "path"` attribute into the predicate path. It is
+/// accepted on every field type; it matters most for those whose presence on
+/// the wire isn't already implied by equality with their zero value (message,
+/// map, repeated). Mirrors serde's `skip_serializing_if`.
+pub(super) fn skip_if_attr(cx: &Ctxt, attr: &Meta) -> Option<syn::Path> {
+    if !attr.path().is_ident("skip_if") {
+        return None;
+    }
+    match *attr {
         Meta::NameValue(MetaNameValue {
             lit: Lit::Str(ref lit),
             ..
-        }) => lit
-            .value()
-            .split(',')
-            .map(|s| s.trim().parse::<u32>().map_err(Error::from))
-            .collect::<Result<Vec<u32>, _>>()
-            .map(Some),
-        _ => bail!("invalid tag attribute: {:?}", attr),
+        }) => match lit.parse::<syn::Path>() {
+            Ok(path) => Some(path),
+            Err(err) => {
+                cx.error_spanned_by(lit, err);
+                None
+            }
+        },
+        _ => {
+            cx.error_spanned_by(
+                attr,
+                "invalid skip_if attribute, expected `skip_if = \"path\"`",
+            );
+            None
+        }
+    }
+}
+
+/// Parses an `alias_tags = "2, 3"` (or `alias_tags(2, 3)`) attribute into the
+/// list of alias tags a field should also accept on decode, in addition to
+/// its primary `tag`. Structurally identical to `tags_attr`, but additive
+/// rather than replacing the primary tag: callers are expected to keep
+/// encoding under `tag` and only widen the decode-side dispatch to also
+/// route these tags into the field.
+pub(super) fn alias_tags_attr(cx: &Ctxt, attr: &Meta) -> Option<Vec<u32>> {
+    if !attr.path().is_ident("alias_tags") {
+        return None;
+    }
+    parse_u32_list(cx, attr, "alias_tags")
+}
+
+/// Parses a `rename = "..."` attribute into an explicit external-name
+/// override for a single field, taking precedence over any container-level
+/// `rename_all` rule. Mirrors serde's per-field `rename`.
+pub(super) fn rename_attr(cx: &Ctxt, attr: &Meta) -> Option<String> {
+    if !attr.path().is_ident("rename") {
+        return None;
+    }
+    match *attr {
+        Meta::NameValue(MetaNameValue {
+            lit: Lit::Str(ref lit),
+            ..
+        }) => Some(lit.value()),
+        _ => {
+            cx.error_spanned_by(
+                attr,
+                "invalid rename attribute, expected `rename = \"...\"`",
+            );
+            None
+        }
+    }
+}
+
+/// Parses a container-level `rename_all = "..."` attribute into a
+/// `RenameRule`. Mirrors serde's `rename_all`.
+pub(super) fn rename_all_attr(cx: &Ctxt, attr: &Meta) -> Option<RenameRule> {
+    if !attr.path().is_ident("rename_all") {
+        return None;
+    }
+    match *attr {
+        Meta::NameValue(MetaNameValue {
+            lit: Lit::Str(ref lit),
+            ..
+        }) => match RenameRule::from_str(&lit.value()) {
+            Some(rule) => Some(rule),
+            None => {
+                cx.error_spanned_by(lit, format!("unknown rename_all rule: {:?}", lit.value()));
+                None
+            }
+        },
+        _ => {
+            cx.error_spanned_by(
+                attr,
+                "invalid rename_all attribute, expected `rename_all = \"...\"`",
+            );
+            None
+        }
+    }
+}
+
+/// A case-conversion rule applied to a Rust field identifier (which is
+/// always `snake_case`) to compute its external JSON/reflection name.
+/// Modeled on serde_derive's `RenameRule`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenameRule {
+    /// Keep the field's Rust identifier as-is.
+    None,
+    /// Rename to "lowercase" style.
+    LowerCase,
+    /// Rename to "UPPERCASE" style.
+    UpperCase,
+    /// Rename to "PascalCase" style.
+    PascalCase,
+    /// Rename to "camelCase" style.
+    CamelCase,
+    /// Rename to "snake_case" style (a no-op, since identifiers already are).
+    SnakeCase,
+    /// Rename to "SCREAMING_SNAKE_CASE" style.
+    ScreamingSnakeCase,
+    /// Rename to "kebab-case" style.
+    KebabCase,
+    /// Rename to "SCREAMING-KEBAB-CASE" style.
+    ScreamingKebabCase,
+}
+
+static RENAME_RULES: &[(&str, RenameRule)] = &[
+    ("lowercase", RenameRule::LowerCase),
+    ("UPPERCASE", RenameRule::UpperCase),
+    ("PascalCase", RenameRule::PascalCase),
+    ("camelCase", RenameRule::CamelCase),
+    ("snake_case", RenameRule::SnakeCase),
+    ("SCREAMING_SNAKE_CASE", RenameRule::ScreamingSnakeCase),
+    ("kebab-case", RenameRule::KebabCase),
+    ("SCREAMING-KEBAB-CASE", RenameRule::ScreamingKebabCase),
+];
+
+impl RenameRule {
+    /// Parses a rule name, e.g. `"camelCase"`, as accepted in a `rename_all`
+    /// attribute. Returns `None` if the name isn't recognized.
+    fn from_str(s: &str) -> Option<RenameRule> {
+        RENAME_RULES
+            .iter()
+            .find(|&&(name, _)| name == s)
+            .map(|&(_, rule)| rule)
+    }
+
+    /// Applies the rule to a `snake_case` Rust field identifier, splitting on
+    /// `_` and re-joining per the rule. A leading run of underscores (as used
+    /// to silence an `unused` warning) is preserved verbatim ahead of the
+    /// renamed remainder.
+    pub fn apply_to_field(&self, field: &str) -> String {
+        if *self == RenameRule::None {
+            return field.to_owned();
+        }
+
+        let split = field.find(|c: char| c != '_').unwrap_or(field.len());
+        let (prefix, field) = field.split_at(split);
+
+        let renamed = match *self {
+            RenameRule::None => unreachable!(),
+            RenameRule::LowerCase | RenameRule::SnakeCase => field.to_owned(),
+            RenameRule::UpperCase | RenameRule::ScreamingSnakeCase => field.to_ascii_uppercase(),
+            RenameRule::PascalCase => {
+                let mut pascal = String::with_capacity(field.len());
+                let mut capitalize = true;
+                for ch in field.chars() {
+                    if ch == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        pascal.extend(ch.to_uppercase());
+                        capitalize = false;
+                    } else {
+                        pascal.push(ch);
+                    }
+                }
+                pascal
+            }
+            RenameRule::CamelCase => {
+                let pascal = RenameRule::PascalCase.apply_to_field(field);
+                let mut chars = pascal.chars();
+                match chars.next() {
+                    Some(first) => first.to_ascii_lowercase().to_string() + chars.as_str(),
+                    None => pascal,
+                }
+            }
+            RenameRule::KebabCase => field.replace('_', "-"),
+            RenameRule::ScreamingKebabCase => field.to_ascii_uppercase().replace('_', "-"),
+        };
+
+        format!("{}{}", prefix, renamed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RenameRule;
+
+    #[test]
+    fn apply_to_field_covers_every_rule() {
+        let cases = [
+            (RenameRule::None, "foo_bar", "foo_bar"),
+            (RenameRule::LowerCase, "foo_bar", "foo_bar"),
+            (RenameRule::UpperCase, "foo_bar", "FOO_BAR"),
+            (RenameRule::PascalCase, "foo_bar", "FooBar"),
+            (RenameRule::CamelCase, "foo_bar", "fooBar"),
+            (RenameRule::SnakeCase, "foo_bar", "foo_bar"),
+            (RenameRule::ScreamingSnakeCase, "foo_bar", "FOO_BAR"),
+            (RenameRule::KebabCase, "foo_bar", "foo-bar"),
+            (RenameRule::ScreamingKebabCase, "foo_bar", "FOO-BAR"),
+        ];
+
+        for (rule, field, expected) in cases {
+            assert_eq!(rule.apply_to_field(field), expected, "{:?}", rule);
+        }
+    }
+
+    #[test]
+    fn apply_to_field_preserves_leading_underscores() {
+        assert_eq!(
+            RenameRule::CamelCase.apply_to_field("__foo_bar"),
+            "__fooBar"
+        );
+        assert_eq!(RenameRule::PascalCase.apply_to_field("_foo_bar"), "_FooBar");
+        assert_eq!(RenameRule::KebabCase.apply_to_field("_foo_bar"), "_foo-bar");
     }
 }